@@ -1,5 +1,7 @@
 use std::{ffi::{CStr, CString}, ptr::null};
+use std::path::Path;
 use crate::backend::current::TEMP_REGS;
+use crate::cruby::*;
 use std::os::raw::{c_char, c_int, c_uint};
 
 // Command-line options
@@ -63,8 +65,9 @@ pub struct Options {
     pub verify_ctx: bool,
 }
 
-// Initialize the options to default values
-pub static mut OPTIONS: Options = Options {
+// Compiled-in default option values, kept separately so introspection can
+// report the default alongside the (possibly mutated) current value.
+pub const DEFAULT_OPTIONS: Options = Options {
     exec_mem_size: 128 * 1024 * 1024,
     call_threshold: 30,
     cold_threshold: 200_000,
@@ -84,19 +87,386 @@ pub static mut OPTIONS: Options = Options {
     dump_iseq_disasm: None,
 };
 
-/// YJIT option descriptions for `ruby --help`.
-static YJIT_OPTIONS: [(&str, &str); 9] = [
-    ("--yjit-stats",                    "Enable collecting YJIT statistics"),
-    ("--yjit-trace-exits",              "Record Ruby source location when exiting from generated code"),
-    ("--yjit-trace-exits-sample-rate",  "Trace exit locations only every Nth occurrence"),
-    ("--yjit-exec-mem-size=num",        "Size of executable memory block in MiB (default: 128)"),
-    ("--yjit-disable-code-gc",          "Don't run code GC after exhausting exec-mem-size"),
-    ("--yjit-call-threshold=num",       "Number of calls to trigger JIT (default: 30)"),
-    ("--yjit-cold-threshold=num",       "Global call after which ISEQs not compiled (default: 200K)"),
-    ("--yjit-max-versions=num",         "Maximum number of versions per basic block (default: 4)"),
-    ("--yjit-greedy-versioning",        "Greedy versioning mode (default: disabled)"),
+// Initialize the options to default values
+pub static mut OPTIONS: Options = DEFAULT_OPTIONS;
+
+/// Graduated optimization-level presets, modeled on a compiler's `OptLevel`.
+/// Selected with `--yjit-opt-level`; each level expands into a coherent set of
+/// defaults for the individual tuning knobs. The preset never overwrites a knob
+/// set by its own flag in the same source, so an explicit `--yjit-max-versions=N`
+/// still overrides the value the level would have chosen, whichever comes first.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OptLevel {
+    /// Minimal, generic code with fast warmup (`--yjit-opt-level=0`)
+    No,
+    /// Keep type propagation but few versions (`--yjit-opt-level=1`)
+    Less,
+    /// Today's defaults (`--yjit-opt-level=2`)
+    Default,
+    /// Greedy versioning, many versions, eager compilation (`--yjit-opt-level=3`)
+    Aggressive,
+    /// Size-oriented: cap executable memory and versions (`--yjit-opt-level=s`)
+    Size,
+}
+
+impl OptLevel {
+    /// Parse the value following `--yjit-opt-level=`.
+    fn parse(opt_val: &str) -> Option<OptLevel> {
+        match opt_val {
+            "0" => Some(OptLevel::No),
+            "1" => Some(OptLevel::Less),
+            "2" => Some(OptLevel::Default),
+            "3" => Some(OptLevel::Aggressive),
+            "s" => Some(OptLevel::Size),
+            _ => None,
+        }
+    }
+}
+
+/// Records which preset-owned knobs were set explicitly (via their own flag)
+/// *within a single source*. A preset never clobbers a knob the same source
+/// set explicitly, regardless of the order the level and the flag appear. This
+/// is scoped per source (each layer starts with a fresh [`ExplicitlySet`]) so
+/// a higher-precedence source's preset still overrides a lower source's value.
+#[derive(Clone, Copy, Default)]
+struct ExplicitlySet {
+    exec_mem_size: bool,
+    call_threshold: bool,
+    greedy_versioning: bool,
+    no_type_prop: bool,
+    max_versions: bool,
+}
+
+/// Explicit-knob tracker for the command-line layer. Unlike the config/env
+/// layers, which each own a local tracker, the command line is fed to
+/// `parse_option` one flag at a time by the C caller, so its tracker must
+/// persist across calls. It is reset to `default()` when the command-line
+/// layer begins (at the end of `rb_yjit_parse_env_options`).
+static mut CLI_EXPLICIT: ExplicitlySet = ExplicitlySet {
+    exec_mem_size: false,
+    call_threshold: false,
+    greedy_versioning: false,
+    no_type_prop: false,
+    max_versions: false,
+};
+
+/// Expand an optimization-level preset into the individual tuning knobs. The
+/// preset-owned knobs are first reset to their compiled-in defaults (so the
+/// *last* `--yjit-opt-level` within a source fully determines the preset
+/// portion and stacking levels can't leak state), then the selected level's
+/// values are applied. Knobs set explicitly in the same source are left alone,
+/// so an explicit flag wins over the preset in either order.
+fn apply_opt_level(opts: &mut Options, explicit: &ExplicitlySet, level: OptLevel) {
+    if !explicit.exec_mem_size { opts.exec_mem_size = DEFAULT_OPTIONS.exec_mem_size }
+    if !explicit.call_threshold { opts.call_threshold = DEFAULT_OPTIONS.call_threshold }
+    if !explicit.greedy_versioning { opts.greedy_versioning = DEFAULT_OPTIONS.greedy_versioning }
+    if !explicit.no_type_prop { opts.no_type_prop = DEFAULT_OPTIONS.no_type_prop }
+    if !explicit.max_versions { opts.max_versions = DEFAULT_OPTIONS.max_versions }
+
+    match level {
+        OptLevel::No => {
+            if !explicit.max_versions { opts.max_versions = 1 }
+            if !explicit.no_type_prop { opts.no_type_prop = true }
+            if !explicit.call_threshold { opts.call_threshold = 200 }
+        }
+        OptLevel::Less => {
+            if !explicit.max_versions { opts.max_versions = 2 }
+        }
+        // Level 2 is the compiled-in default, nothing to expand.
+        OptLevel::Default => {}
+        OptLevel::Aggressive => {
+            if !explicit.greedy_versioning { opts.greedy_versioning = true }
+            if !explicit.max_versions { opts.max_versions = 8 }
+            if !explicit.call_threshold { opts.call_threshold = 10 }
+        }
+        OptLevel::Size => {
+            if !explicit.exec_mem_size { opts.exec_mem_size = 64 * 1024 * 1024 }
+            if !explicit.max_versions { opts.max_versions = 1 }
+        }
+    }
+}
+
+/// The kind of value an option accepts. Drives the placeholder shown in
+/// `ruby --help`; diagnostics come from each option's `apply` function.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OptKind {
+    /// A bare flag that takes no value (e.g. `--yjit-pause`).
+    Flag,
+    /// An unsigned integer.
+    UInt,
+    /// One of a fixed set of string values.
+    Enum,
+    /// One of a fixed set of string values, where the empty value is also
+    /// accepted so the option is normally given bare (e.g. `--yjit-stats`).
+    OptEnum,
+    /// A filesystem path (may be empty).
+    Path,
+    /// A free-form string.
+    Str,
+}
+
+impl OptKind {
+    /// The `=placeholder` suffix shown after the option name in `--help`.
+    fn placeholder(self) -> &'static str {
+        match self {
+            // Bare forms: no value shown in `--help`.
+            OptKind::Flag | OptKind::OptEnum => "",
+            OptKind::UInt => "=num",
+            OptKind::Enum => "=val",
+            OptKind::Path => "=dir",
+            OptKind::Str => "=str",
+        }
+    }
+}
+
+/// A single YJIT option. The table of these is the one source of truth: it
+/// drives `parse_option` and feeds `rb_yjit_show_usage`, so adding an option
+/// is a one-line table entry rather than edits in two places that drift apart.
+struct OptionDesc {
+    /// Name as it appears after `--yjit-` (e.g. `"call-threshold"`).
+    name: &'static str,
+    /// Value kind, used for `--help` rendering.
+    kind: OptKind,
+    /// Parse and validate `opt_val`, applying it to `opts` and recording any
+    /// preset-owned knob it sets in `explicit`. Returns a human-readable reason
+    /// on rejection.
+    apply: fn(&mut Options, &mut ExplicitlySet, &str) -> Result<(), String>,
+    /// Render the option's value from an `Options` struct, used by
+    /// introspection to report both the current and the default value. `None`
+    /// for pseudo-options (such as the `opt-level` preset) that have no field
+    /// of their own and so are excluded from introspection.
+    show: Option<fn(&Options) -> String>,
+    /// Help text for `ruby --help`, or `None` for debug-only options that are
+    /// hidden from usage output.
+    help: Option<&'static str>,
+}
+
+/// Parse an unsigned integer, mapping a parse failure to a diagnostic reason.
+fn parse_uint(opt_val: &str) -> Result<usize, String> {
+    opt_val.parse::<usize>().map_err(|_| "expected an integer".to_string())
+}
+
+/// Whether diagnostics should be colorized. Mirrors `ColorConfig`: highlighting
+/// is only enabled when stderr is attached to a terminal.
+fn diagnostics_use_color() -> bool {
+    extern "C" {
+        fn isatty(fd: c_int) -> c_int;
+    }
+    // File descriptor 2 is stderr, where diagnostics are written.
+    unsafe { isatty(2) != 0 }
+}
+
+/// Emit a fatal diagnostic for a rejected option and return. `option` is the
+/// offending name as it appeared after `--yjit-`, and `reason` says what was
+/// wrong (e.g. the accepted range or set of values). The caller rejects the
+/// option after calling this.
+fn early_error(option: &str, reason: &str) {
+    let (hi, reset) = if diagnostics_use_color() { ("\x1b[1;31m", "\x1b[0m") } else { ("", "") };
+    eprintln!("{hi}error{reset}: invalid option `--yjit-{option}`: {reason}");
+}
+
+/// Emit a non-fatal diagnostic. Unlike `early_error`, parsing continues; when
+/// the warning is about an option, its value has already been applied.
+fn early_warn(reason: &str) {
+    let (hi, reset) = if diagnostics_use_color() { ("\x1b[1;33m", "\x1b[0m") } else { ("", "") };
+    eprintln!("{hi}warning{reset}: {reason}");
+}
+
+/// Every YJIT option, in the order shown by `ruby --help`.
+static YJIT_OPTION_TABLE: &[OptionDesc] = &[
+    OptionDesc {
+        name: "opt-level", kind: OptKind::Enum,
+        apply: |opts, explicit, val| match OptLevel::parse(val) {
+            Some(level) => { apply_opt_level(opts, explicit, level); Ok(()) }
+            None => Err("value must be one of 0, 1, 2, 3, s".to_string()),
+        },
+        // A preset, not a stored field; excluded from introspection.
+        show: None,
+        help: Some("Optimization-level preset 0..3 or s (default: 2)"),
+    },
+    OptionDesc {
+        name: "exec-mem-size", kind: OptKind::UInt,
+        apply: |opts, explicit, val| {
+            let n = parse_uint(val)?;
+            if n == 0 || n > 2 * 1024 * 1024 {
+                return Err("value must be between 1 and 2097152 MiB".to_string());
+            }
+            // Convert from MiB to bytes internally for convenience
+            opts.exec_mem_size = n * 1024 * 1024;
+            explicit.exec_mem_size = true;
+            Ok(())
+        },
+        show: Some(|o| (o.exec_mem_size / (1024 * 1024)).to_string()),
+        help: Some("Size of executable memory block in MiB (default: 128)"),
+    },
+    OptionDesc {
+        name: "call-threshold", kind: OptKind::UInt,
+        apply: |opts, explicit, val| {
+            opts.call_threshold = parse_uint(val)?;
+            explicit.call_threshold = true;
+            Ok(())
+        },
+        show: Some(|o| o.call_threshold.to_string()),
+        help: Some("Number of calls to trigger JIT (default: 30)"),
+    },
+    OptionDesc {
+        name: "cold-threshold", kind: OptKind::UInt,
+        apply: |opts, _explicit, val| {
+            opts.cold_threshold = parse_uint(val)?;
+            Ok(())
+        },
+        show: Some(|o| o.cold_threshold.to_string()),
+        help: Some("Global call after which ISEQs not compiled (default: 200K)"),
+    },
+    OptionDesc {
+        name: "max-versions", kind: OptKind::UInt,
+        apply: |opts, explicit, val| {
+            opts.max_versions = parse_uint(val)?;
+            explicit.max_versions = true;
+            Ok(())
+        },
+        show: Some(|o| o.max_versions.to_string()),
+        help: Some("Maximum number of versions per basic block (default: 4)"),
+    },
+    OptionDesc {
+        name: "pause", kind: OptKind::Flag,
+        apply: |opts, _explicit, val| { expect_flag(val)?; opts.pause = true; Ok(()) },
+        show: Some(|o| o.pause.to_string()),
+        help: None,
+    },
+    OptionDesc {
+        name: "disable-code-gc", kind: OptKind::Flag,
+        apply: |opts, _explicit, val| { expect_flag(val)?; opts.disable_code_gc = true; Ok(()) },
+        show: Some(|o| o.disable_code_gc.to_string()),
+        help: Some("Don't run code GC after exhausting exec-mem-size"),
+    },
+    OptionDesc {
+        name: "temp-regs", kind: OptKind::UInt,
+        apply: |opts, _explicit, val| {
+            let n = parse_uint(val)?;
+            if n > TEMP_REGS.len() {
+                return Err(format!("value must be <= {}", TEMP_REGS.len()));
+            }
+            opts.num_temp_regs = n;
+            Ok(())
+        },
+        show: Some(|o| o.num_temp_regs.to_string()),
+        help: None,
+    },
+    OptionDesc {
+        name: "dump-disasm", kind: OptKind::Path,
+        apply: |opts, _explicit, val| {
+            match val {
+                "" => opts.dump_disasm = Some(DumpDisasm::Stdout),
+                directory => {
+                    let pid = std::process::id();
+                    let path = format!("{directory}/yjit_{pid}.log");
+                    println!("YJIT disasm dump: {path}");
+                    opts.dump_disasm = Some(DumpDisasm::File(path));
+                }
+            }
+            Ok(())
+        },
+        show: Some(|o| match &o.dump_disasm {
+            None => "false".to_string(),
+            Some(DumpDisasm::Stdout) => "stdout".to_string(),
+            Some(DumpDisasm::File(path)) => path.clone(),
+        }),
+        help: None,
+    },
+    OptionDesc {
+        name: "dump-iseq-disasm", kind: OptKind::Str,
+        apply: |opts, _explicit, val| { opts.dump_iseq_disasm = Some(val.to_string()); Ok(()) },
+        show: Some(|o| o.dump_iseq_disasm.clone().unwrap_or_else(|| "false".to_string())),
+        help: None,
+    },
+    OptionDesc {
+        name: "greedy-versioning", kind: OptKind::Flag,
+        apply: |opts, explicit, val| { expect_flag(val)?; opts.greedy_versioning = true; explicit.greedy_versioning = true; Ok(()) },
+        show: Some(|o| o.greedy_versioning.to_string()),
+        help: Some("Greedy versioning mode (default: disabled)"),
+    },
+    OptionDesc {
+        name: "no-type-prop", kind: OptKind::Flag,
+        apply: |opts, explicit, val| { expect_flag(val)?; opts.no_type_prop = true; explicit.no_type_prop = true; Ok(()) },
+        show: Some(|o| o.no_type_prop.to_string()),
+        help: None,
+    },
+    OptionDesc {
+        name: "stats", kind: OptKind::OptEnum,
+        apply: |opts, _explicit, val| match val {
+            "" => { opts.gen_stats = true; Ok(()) }
+            "quiet" => { opts.gen_stats = true; opts.print_stats = false; Ok(()) }
+            _ => Err("value must be empty or \"quiet\"".to_string()),
+        },
+        show: Some(|o| o.gen_stats.to_string()),
+        help: Some("Enable collecting YJIT statistics"),
+    },
+    OptionDesc {
+        name: "trace-exits", kind: OptKind::Flag,
+        apply: |opts, _explicit, val| {
+            expect_flag(val)?;
+            opts.gen_trace_exits = true;
+            opts.gen_stats = true;
+            opts.trace_exits_sample_rate = 0;
+            Ok(())
+        },
+        show: Some(|o| o.gen_trace_exits.to_string()),
+        help: Some("Record Ruby source location when exiting from generated code"),
+    },
+    OptionDesc {
+        name: "trace-exits-sample-rate", kind: OptKind::UInt,
+        apply: |opts, _explicit, val| {
+            let n = parse_uint(val)?;
+            opts.gen_trace_exits = true;
+            opts.gen_stats = true;
+            opts.trace_exits_sample_rate = n;
+            if !is_prime_or_trivial(n) {
+                early_warn("value should be prime for accurate sampling; a non-prime sampling rate can result in less accurate sampling data");
+            }
+            Ok(())
+        },
+        show: Some(|o| o.trace_exits_sample_rate.to_string()),
+        help: Some("Trace exit locations only every Nth occurrence"),
+    },
+    OptionDesc {
+        name: "dump-insns", kind: OptKind::Flag,
+        apply: |opts, _explicit, val| { expect_flag(val)?; opts.dump_insns = true; Ok(()) },
+        show: Some(|o| o.dump_insns.to_string()),
+        help: None,
+    },
+    OptionDesc {
+        name: "verify-ctx", kind: OptKind::Flag,
+        apply: |opts, _explicit, val| { expect_flag(val)?; opts.verify_ctx = true; Ok(()) },
+        show: Some(|o| o.verify_ctx.to_string()),
+        help: None,
+    },
 ];
 
+/// Reject a value passed to a flag that takes no value.
+fn expect_flag(opt_val: &str) -> Result<(), String> {
+    if opt_val.is_empty() {
+        Ok(())
+    } else {
+        Err("this option takes no value".to_string())
+    }
+}
+
+/// A sampling rate is only accurate when it is 0, 1, or prime.
+fn is_prime_or_trivial(n: usize) -> bool {
+    if n <= 1 {
+        return true;
+    }
+    let mut i = 2;
+    while i * i <= n {
+        if n % i == 0 {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum DumpDisasm {
     // Dump to stdout
@@ -137,138 +507,174 @@ pub fn parse_option(str_ptr: *const std::os::raw::c_char) -> Option<()> {
     let c_str: &CStr = unsafe { CStr::from_ptr(str_ptr) };
     let opt_str: &str = c_str.to_str().ok()?;
     //println!("{}", opt_str);
+    parse_option_str(opt_str)
+}
 
+/// Split `opt_str` (the text after "--yjit-") into name/value and apply it onto
+/// `opts`, recording any preset-owned knob it sets in `explicit`. Shared by the
+/// command line and the environment/config layers so every source goes through
+/// the exact same descriptor table. Returns a diagnostic reason on rejection.
+fn apply_option_into(opts: &mut Options, explicit: &mut ExplicitlySet, opt_str: &str) -> Result<(), String> {
     // Split the option name and value strings
     // Note that some options do not contain an assignment
-    let parts = opt_str.split_once('=');
-    let (opt_name, opt_val) = match parts {
+    let (opt_name, opt_val) = match opt_str.split_once('=') {
         Some((before_eq, after_eq)) => (before_eq, after_eq),
         None => (opt_str, ""),
     };
 
-    // Match on the option name and value strings
-    match (opt_name, opt_val) {
-        ("", "") => (), // Simply --yjit
-
-        ("exec-mem-size", _) => match opt_val.parse::<usize>() {
-            Ok(n) => {
-                if n == 0 || n > 2 * 1024 * 1024 {
-                    return None
-                }
-
-                // Convert from MiB to bytes internally for convenience
-                unsafe { OPTIONS.exec_mem_size = n * 1024 * 1024 }
-            }
-            Err(_) => {
-                return None;
-            }
-        },
-
-        ("call-threshold", _) => match opt_val.parse() {
-            Ok(n) => unsafe { OPTIONS.call_threshold = n },
-            Err(_) => {
-                return None;
-            }
-        },
+    // Simply --yjit
+    if opt_name.is_empty() && opt_val.is_empty() {
+        return Ok(());
+    }
 
-        ("cold-threshold", _) => match opt_val.parse() {
-            Ok(n) => unsafe { OPTIONS.cold_threshold = n },
-            Err(_) => {
-                return None;
-            }
-        },
+    // Look the option up in the descriptor table and let it parse/validate and
+    // apply its own value.
+    for desc in YJIT_OPTION_TABLE.iter() {
+        if desc.name == opt_name {
+            return (desc.apply)(opts, explicit, opt_val);
+        }
+    }
 
-        ("max-versions", _) => match opt_val.parse() {
-            Ok(n) => unsafe { OPTIONS.max_versions = n },
-            Err(_) => {
-                return None;
-            }
-        },
+    Err("unknown option".to_string())
+}
 
-        ("pause", "") => unsafe {
-            OPTIONS.pause = true;
-        },
+/// The option name part of `opt_str`, for use in diagnostics.
+fn option_name_of(opt_str: &str) -> &str {
+    opt_str.split_once('=').map(|(name, _)| name).unwrap_or(opt_str)
+}
 
-        ("disable-code-gc", "") => unsafe {
-            OPTIONS.disable_code_gc = true;
+/// Parse a single command-line option given as the text after "--yjit-". The
+/// command line is the highest-precedence layer, applied on top of the
+/// already-merged config/env layers; `CLI_EXPLICIT` tracks its explicit knobs.
+fn parse_option_str(opt_str: &str) -> Option<()> {
+    match unsafe { apply_option_into(&mut OPTIONS, &mut CLI_EXPLICIT, opt_str) } {
+        Ok(()) => Some(()),
+        // Fatal: reject the option and tell the user exactly why.
+        Err(reason) => {
+            early_error(option_name_of(opt_str), &reason);
+            None
         }
+    }
+}
 
-        ("temp-regs", _) => match opt_val.parse() {
-            Ok(n) => {
-                assert!(n <= TEMP_REGS.len(), "--yjit-temp-regs must be <= {}", TEMP_REGS.len());
-                unsafe { OPTIONS.num_temp_regs = n }
-            }
-            Err(_) => {
-                return None;
-            }
-        },
-
-        ("dump-disasm", _) => match opt_val {
-            "" => unsafe { OPTIONS.dump_disasm = Some(DumpDisasm::Stdout) },
-            directory => {
-                let pid = std::process::id();
-                let path = format!("{directory}/yjit_{pid}.log");
-                println!("YJIT disasm dump: {path}");
-                unsafe { OPTIONS.dump_disasm = Some(DumpDisasm::File(path)) }
+/// Environment variable holding a whitespace-separated list of `--yjit-*`
+/// options, e.g. `RUBY_YJIT_OPTS="--yjit-call-threshold=10 --yjit-stats"`.
+const YJIT_ENV_VAR: &str = "RUBY_YJIT_OPTS";
+
+/// Environment variable naming a config file of `--yjit-*` options, one per
+/// line (blank lines and `#` comments are ignored).
+const YJIT_CONFIG_ENV_VAR: &str = "RUBY_YJIT_CONFIG";
+
+/// Apply one source's worth of `--yjit-*` tokens as a single layer on top of
+/// the current `OPTIONS`. The layer gets its own fresh explicit-knob tracker so
+/// that a preset in a higher-precedence source overrides a value a lower source
+/// set, while an explicit flag still wins over a preset within this same layer.
+fn apply_option_layer(tokens: &[&str]) {
+    let mut explicit = ExplicitlySet::default();
+    for &token in tokens {
+        // Strip the shared `--yjit` prefix (and the separating dash) so the
+        // rest is parsed identically to a command-line flag.
+        let rest = match token.strip_prefix("--yjit") {
+            Some(rest) => rest.strip_prefix('-').unwrap_or(rest),
+            None => {
+                // `early_error` prefixes `--yjit-`, which this token lacks.
+                let (hi, reset) = if diagnostics_use_color() { ("\x1b[1;31m", "\x1b[0m") } else { ("", "") };
+                eprintln!("{hi}error{reset}: invalid option `{token}`: unknown option");
+                continue;
             }
-         },
+        };
+        if let Err(reason) = unsafe { apply_option_into(&mut OPTIONS, &mut explicit, rest) } {
+            early_error(option_name_of(rest), &reason);
+        }
+    }
+}
 
-        ("dump-iseq-disasm", _) => unsafe {
-            OPTIONS.dump_iseq_disasm = Some(opt_val.to_string());
-        },
+/// Apply every whitespace-separated option token in `opts` as one layer.
+fn apply_options_from_str(opts: &str) {
+    let tokens: Vec<&str> = opts.split_whitespace().collect();
+    apply_option_layer(&tokens);
+}
 
-        ("greedy-versioning", "") => unsafe { OPTIONS.greedy_versioning = true },
-        ("no-type-prop", "") => unsafe { OPTIONS.no_type_prop = true },
-        ("stats", _) => match opt_val {
-            "" => unsafe { OPTIONS.gen_stats = true },
-            "quiet" => {
-                unsafe { OPTIONS.gen_stats = true }
-                unsafe { OPTIONS.print_stats = false }
-            },
-            _ => {
-                return None;
-            }
-        },
-        ("trace-exits", "") => unsafe { OPTIONS.gen_trace_exits = true; OPTIONS.gen_stats = true; OPTIONS.trace_exits_sample_rate = 0 },
-        ("trace-exits-sample-rate", sample_rate) => unsafe { OPTIONS.gen_trace_exits = true; OPTIONS.gen_stats = true; OPTIONS.trace_exits_sample_rate = sample_rate.parse().unwrap(); },
-        ("dump-insns", "") => unsafe { OPTIONS.dump_insns = true },
-        ("verify-ctx", "") => unsafe { OPTIONS.verify_ctx = true },
-
-        // Option name not recognized
-        _ => {
-            return None;
+/// Apply options from a config file: one option per line, with blank lines and
+/// `#` comments ignored. A missing or unreadable file is reported and skipped.
+fn apply_options_from_config_file(path: &Path) {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            early_warn(&format!("could not read YJIT config file {}: {err}", path.display()));
+            return;
         }
+    };
+    let tokens: Vec<&str> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    apply_option_layer(&tokens);
+}
+
+/// Apply YJIT options from the lower-precedence sources before the C caller
+/// processes command-line flags. Precedence mirrors compiler convention:
+/// built-in defaults, then config file, then environment variable, then the
+/// command line (applied last, so it wins). Each source is a distinct layer
+/// merged on top of the previous one.
+#[no_mangle]
+pub extern "C" fn rb_yjit_parse_env_options() {
+    // Config file first (lowest precedence above the built-in defaults)...
+    if let Some(path) = std::env::var_os(YJIT_CONFIG_ENV_VAR) {
+        apply_options_from_config_file(Path::new(&path));
     }
+    // ...then the environment variable, which overrides the config file.
+    if let Ok(opts) = std::env::var(YJIT_ENV_VAR) {
+        apply_options_from_str(&opts);
+    }
+    // The command-line layer begins now; start it with a fresh explicit-knob
+    // tracker so a CLI preset overrides values from the config file or env.
+    unsafe { CLI_EXPLICIT = ExplicitlySet::default() }
+}
 
-    // before we continue, check that sample_rate is either 0 or a prime number
-    let trace_sample_rate = unsafe { OPTIONS.trace_exits_sample_rate };
-    if trace_sample_rate > 1 {
-        let mut i = 2;
-        while i*i <= trace_sample_rate {
-            if trace_sample_rate % i == 0 {
-                println!("Warning: using a non-prime number as your sampling rate can result in less accurate sampling data");
-                return Some(());
-            }
-            i += 1;
+/// Build a Ruby hash describing the options currently in effect, keyed by
+/// option name symbol. Each value is a hash with `:value` (the current value),
+/// `:default` (the compiled-in default), and `:description` (the help text, or
+/// `nil` for debug-only options). Surfaced to Ruby through `RubyVM::YJIT` so
+/// tooling can dump the resolved configuration — valuable now that env-var and
+/// config-file layering can make the effective config non-obvious.
+#[no_mangle]
+pub extern "C" fn rb_yjit_get_options() -> VALUE {
+    let current = unsafe { OPTIONS.clone() };
+    let hash = unsafe { rb_hash_new() };
+    for desc in YJIT_OPTION_TABLE.iter() {
+        // Pseudo-options (e.g. the `opt-level` preset) have no field of their
+        // own to report, so they are excluded from introspection.
+        let Some(show) = desc.show else { continue };
+        let entry = unsafe { rb_hash_new() };
+        let value = rust_str_to_ruby(&show(&current));
+        let default = rust_str_to_ruby(&show(&DEFAULT_OPTIONS));
+        let description = match desc.help {
+            Some(help) => rust_str_to_ruby(help),
+            None => Qnil,
+        };
+        unsafe {
+            rb_hash_aset(entry, rust_str_to_sym("value"), value);
+            rb_hash_aset(entry, rust_str_to_sym("default"), default);
+            rb_hash_aset(entry, rust_str_to_sym("description"), description);
+            rb_hash_aset(hash, rust_str_to_sym(desc.name), entry);
         }
     }
-
-    // dbg!(unsafe {OPTIONS});
-
-    // Option successfully parsed
-    return Some(());
+    hash
 }
 
 /// Print YJIT options for `ruby --help`. `width` is width of option parts, and
 /// `columns` is indent width of descriptions.
 #[no_mangle]
 pub extern "C" fn rb_yjit_show_usage(help: c_int, highlight: c_int, width: c_uint, columns: c_int) {
-    for &(name, description) in YJIT_OPTIONS.iter() {
+    for desc in YJIT_OPTION_TABLE.iter() {
+        let Some(description) = desc.help else { continue };
         extern "C" {
             fn ruby_show_usage_line(name: *const c_char, secondary: *const c_char, description: *const c_char,
                                     help: c_int, highlight: c_int, width: c_uint, columns: c_int);
         }
-        let name = CString::new(name).unwrap();
+        let name = CString::new(format!("--yjit-{}{}", desc.name, desc.kind.placeholder())).unwrap();
         let description = CString::new(description).unwrap();
         unsafe { ruby_show_usage_line(name.as_ptr(), null(), description.as_ptr(), help, highlight, width, columns) }
     }